@@ -0,0 +1,94 @@
+use crate::config::ClientServiceConfig;
+use crate::protocol::{
+    self, read_control_cmd, read_data_channel_cmd, Ack, ControlChannelCmd, DataChannelCmd, Hello,
+};
+use anyhow::{Context, Result};
+use tokio::io::{copy_bidirectional, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::{debug, error, instrument};
+
+// Maintains one service's control channel on the client side. Its one job as
+// far as the server's heartbeat watchdog (see `ControlChannel::run` in
+// `server.rs`) is concerned: ack every command the server sends down this
+// connection, which is what lets the server tell this client is actually
+// still there, rather than just that its own write succeeded.
+pub struct ControlChannel {
+    conn: TcpStream,
+    service: ClientServiceConfig,
+    remote_addr: String,
+    nonce: protocol::Digest,
+}
+
+impl ControlChannel {
+    pub fn new(
+        conn: TcpStream,
+        service: ClientServiceConfig,
+        remote_addr: String,
+        nonce: protocol::Digest,
+    ) -> ControlChannel {
+        ControlChannel {
+            conn,
+            service,
+            remote_addr,
+            nonce,
+        }
+    }
+
+    #[instrument(skip(self), fields(service = %self.service.name))]
+    pub async fn run(mut self) -> Result<()> {
+        let ack = bincode::serialize(&Ack::Ok).unwrap();
+        loop {
+            let cmd = match read_control_cmd(&mut self.conn).await {
+                Ok(cmd) => cmd,
+                Err(e) => {
+                    debug!("Control channel closed: {}", e);
+                    return Ok(());
+                }
+            };
+
+            self.conn
+                .write_all(&ack)
+                .await
+                .with_context(|| "Failed to ack a control channel command")?;
+
+            if let ControlChannelCmd::CreateDataChannel = cmd {
+                let remote_addr = self.remote_addr.clone();
+                let service = self.service.clone();
+                let nonce = self.nonce;
+                tokio::spawn(async move {
+                    if let Err(e) = run_data_channel(remote_addr, service, nonce).await {
+                        error!("Failed to run a data channel: {:#}", e);
+                    }
+                });
+            }
+        }
+    }
+}
+
+// Dials a fresh data channel back to `server.bind_addr` in response to a
+// `CreateDataChannel` command, then relays it to the local service once the
+// server signals `DataChannelCmd::StartForward`.
+async fn run_data_channel(
+    remote_addr: String,
+    service: ClientServiceConfig,
+    nonce: protocol::Digest,
+) -> Result<()> {
+    let mut conn = TcpStream::connect(&remote_addr)
+        .await
+        .with_context(|| format!("Failed to connect to {}", remote_addr))?;
+
+    let hello = Hello::DataChannelHello(protocol::CURRENT_PROTO_VRESION, nonce);
+    conn.write_all(&bincode::serialize(&hello).unwrap()).await?;
+
+    match read_data_channel_cmd(&mut conn).await? {
+        DataChannelCmd::StartForward => {
+            let mut local = TcpStream::connect(&service.local_addr)
+                .await
+                .with_context(|| {
+                    format!("Failed to connect to the local service at {}", service.local_addr)
+                })?;
+            copy_bidirectional(&mut conn, &mut local).await?;
+        }
+    }
+    Ok(())
+}
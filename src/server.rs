@@ -2,8 +2,11 @@ use crate::config::{Config, ServerConfig, ServerServiceConfig, TransportType};
 use crate::multi_map::MultiMap;
 use crate::protocol::Hello::{ControlChannelHello, DataChannelHello};
 use crate::protocol::{
-    self, read_auth, read_hello, Ack, ControlChannelCmd, DataChannelCmd, Hello, HASH_WIDTH_IN_BYTES,
+    self, read_ack, read_auth, read_hello, Ack, ControlChannelCmd, DataChannelCmd, Hello,
+    HASH_WIDTH_IN_BYTES,
 };
+#[cfg(feature = "quic")]
+use crate::transport::QuicTransport;
 #[cfg(feature = "tls")]
 use crate::transport::TlsTransport;
 use crate::transport::{TcpTransport, Transport};
@@ -11,21 +14,24 @@ use anyhow::{anyhow, bail, Context, Result};
 use backoff::backoff::Backoff;
 use backoff::ExponentialBackoff;
 use rand::RngCore;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::io::{self, copy_bidirectional, AsyncWriteExt};
+use std::time::{Duration, Instant};
+use tokio::io::{self, copy_bidirectional, AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{broadcast, mpsc, oneshot, RwLock};
+use tokio::sync::{broadcast, mpsc, oneshot, Mutex, RwLock};
 use tokio::time;
 use tracing::{debug, error, info, info_span, warn, Instrument};
 
 type ServiceDigest = protocol::Digest; // SHA256 of a service name
 type Nonce = protocol::Digest; // Also called `session_key`
 
-const POOL_SIZE: usize = 64; // The number of cached connections
 const CHAN_SIZE: usize = 2048; // The capacity of various chans
+// A control channel is considered dead if this many heartbeat intervals
+// pass without the client acking a heartbeat or `CreateDataChannel` command
+const HEARTBEAT_WATCHDOG_MULTIPLIER: u32 = 3;
 
 // The entrypoint of running a server
 pub async fn run_server(config: &Config, shutdown_rx: broadcast::Receiver<bool>) -> Result<()> {
@@ -51,6 +57,15 @@ pub async fn run_server(config: &Config, shutdown_rx: broadcast::Receiver<bool>)
             #[cfg(not(feature = "tls"))]
             crate::helper::feature_not_compile("tls")
         }
+        TransportType::Quic => {
+            #[cfg(feature = "quic")]
+            {
+                let mut server = Server::<QuicTransport>::from(config).await?;
+                server.run(shutdown_rx).await?;
+            }
+            #[cfg(not(feature = "quic"))]
+            crate::helper::feature_not_compile("quic")
+        }
     }
 
     Ok(())
@@ -73,6 +88,38 @@ struct Server<'a, T: Transport> {
     control_channels: Arc<RwLock<ControlChannelMap<T>>>,
     // Wrapper around the transport layer
     transport: Arc<T>,
+    // Count of in-flight `copy_bidirectional` forwarding sessions, across all
+    // services, so a graceful shutdown knows when it's safe to stop waiting
+    active_sessions: Arc<AtomicUsize>,
+}
+
+// Drops pending requests older than `timeout` from the front of the queue
+// (they're pushed in order, so the oldest is always at the front). A request
+// whose data channel never showed up - lost packet, client failed to dial
+// back, nonce mismatch - would otherwise sit in `pending_requests` forever,
+// eventually wedging `requests_to_send` into never refilling the pool again.
+fn expire_pending_requests(pending: &mut VecDeque<Instant>, timeout: Duration) {
+    while let Some(oldest) = pending.front() {
+        if oldest.elapsed() > timeout {
+            pending.pop_front();
+        } else {
+            break;
+        }
+    }
+}
+
+// How many `CreateDataChannel` requests to send so that idle channels plus
+// ones already requested reach `max`, without sending any at all while that
+// combined total is still at or above `min`. Keeping `pending` in the mix is
+// what stops a burst of visitors from each re-requesting up to `max` while
+// the previous batch of requests hasn't been fulfilled yet.
+fn requests_to_send(idle: usize, pending: usize, min: usize, max: usize) -> usize {
+    let outstanding = idle + pending;
+    if outstanding < min {
+        max.saturating_sub(outstanding)
+    } else {
+        0
+    }
 }
 
 // Generate a hash map of services which is indexed by ServiceDigest
@@ -94,6 +141,7 @@ impl<'a, T: 'static + Transport> Server<'a, T> {
             services: Arc::new(RwLock::new(generate_service_hashmap(config))),
             control_channels: Arc::new(RwLock::new(ControlChannelMap::new())),
             transport: Arc::new(*(T::new(&config.transport).await?)),
+            active_sessions: Arc::new(AtomicUsize::new(0)),
         })
     }
 
@@ -144,8 +192,14 @@ impl<'a, T: 'static + Transport> Server<'a, T> {
 
                             let services = self.services.clone();
                             let control_channels = self.control_channels.clone();
+                            let heartbeat_interval = self.config.heartbeat_interval;
+                            let active_sessions = self.active_sessions.clone();
+                            // Each control channel gets its own subscription, so it can
+                            // stop accepting visitors and requesting data channels as
+                            // soon as the server starts shutting down
+                            let conn_shutdown_rx = shutdown_rx.resubscribe();
                             tokio::spawn(async move {
-                                if let Err(err) = handle_connection(conn, addr, services, control_channels).await.with_context(||"Failed to handle a connection to `server.bind_addr`") {
+                                if let Err(err) = handle_connection(conn, addr, services, control_channels, heartbeat_interval, active_sessions, conn_shutdown_rx).await.with_context(||"Failed to handle a connection to `server.bind_addr`") {
                                     error!("{:?}", err);
                                 }
                             }.instrument(info_span!("handle_connection", %addr)));
@@ -160,6 +214,22 @@ impl<'a, T: 'static + Transport> Server<'a, T> {
             }
         }
 
+        // Stop accepting new connections, but let in-flight forwarding sessions
+        // drain on their own, up to `server.shutdown_timeout`
+        let shutdown_timeout = Duration::from_secs(self.config.shutdown_timeout);
+        let deadline = Instant::now() + shutdown_timeout;
+        while self.active_sessions.load(Ordering::SeqCst) > 0 {
+            if Instant::now() >= deadline {
+                warn!(
+                    "Shutdown timeout ({:?}) elapsed with {} forwarding session(s) still active",
+                    shutdown_timeout,
+                    self.active_sessions.load(Ordering::SeqCst)
+                );
+                break;
+            }
+            time::sleep(Duration::from_millis(200)).await;
+        }
+
         Ok(())
     }
 }
@@ -170,13 +240,25 @@ async fn handle_connection<T: 'static + Transport>(
     addr: SocketAddr,
     services: Arc<RwLock<HashMap<ServiceDigest, ServerServiceConfig>>>,
     control_channels: Arc<RwLock<ControlChannelMap<T>>>,
+    heartbeat_interval: u64,
+    active_sessions: Arc<AtomicUsize>,
+    shutdown_rx: broadcast::Receiver<bool>,
 ) -> Result<()> {
     // Read hello
     let hello = read_hello(&mut conn).await?;
     match hello {
         ControlChannelHello(_, service_digest) => {
-            do_control_channel_handshake(conn, addr, services, control_channels, service_digest)
-                .await?;
+            do_control_channel_handshake(
+                conn,
+                addr,
+                services,
+                control_channels,
+                service_digest,
+                heartbeat_interval,
+                active_sessions,
+                shutdown_rx,
+            )
+            .await?;
         }
         DataChannelHello(_, nonce) => {
             do_data_channel_handshake(conn, control_channels, nonce).await?;
@@ -191,6 +273,9 @@ async fn do_control_channel_handshake<T: 'static + Transport>(
     services: Arc<RwLock<HashMap<ServiceDigest, ServerServiceConfig>>>,
     control_channels: Arc<RwLock<ControlChannelMap<T>>>,
     service_digest: ServiceDigest,
+    heartbeat_interval: u64,
+    active_sessions: Arc<AtomicUsize>,
+    shutdown_rx: broadcast::Receiver<bool>,
 ) -> Result<()> {
     info!("New control channel incomming from {}", addr);
 
@@ -244,9 +329,8 @@ async fn do_control_channel_handshake<T: 'static + Transport>(
         let mut h = control_channels.write().await;
 
         // If there's already a control channel for the service, then drop the old one.
-        // Because a control channel doesn't report back when it's dead,
-        // the handle in the map could be stall, dropping the old handle enables
-        // the client to reconnect.
+        // This can still race with the heartbeat watchdog evicting the same stale
+        // handle, but `remove1` is a no-op in that case, so it's harmless.
         if h.remove1(&service_digest).is_some() {
             warn!(
                 "Dropping previous control channel for digest {}",
@@ -259,7 +343,15 @@ async fn do_control_channel_handshake<T: 'static + Transport>(
             .await?;
 
         info!(service = %service_config.name, "Control channel established");
-        let handle = ControlChannelHandle::new(conn, service_config);
+        let handle = ControlChannelHandle::new(
+            conn,
+            service_config,
+            control_channels.clone(),
+            session_key,
+            heartbeat_interval,
+            active_sessions,
+            shutdown_rx,
+        );
 
         // Insert the new handle
         let _ = h.insert(service_digest, session_key, handle);
@@ -278,7 +370,11 @@ async fn do_data_channel_handshake<T: Transport>(
     match control_channels_guard.get2(&nonce) {
         Some(c_ch) => {
             // Send the data channel to the corresponding control channel
+            // Counted before the send, so the pool's `fetch_sub` on receipt
+            // can't run first and wrap the count below zero
+            c_ch.conn_pool.idle_count.fetch_add(1, Ordering::SeqCst);
             c_ch.conn_pool.data_ch_tx.send(conn).await?;
+            c_ch.conn_pool.pending_requests.lock().await.pop_front();
         }
         None => {
             // TODO: Maybe print IP here
@@ -294,6 +390,29 @@ struct ControlChannel<T: Transport> {
     service: ServerServiceConfig,         // A copy of the corresponding service config
     shutdown_rx: oneshot::Receiver<bool>, // Receives the shutdown signal
     visitor_tx: mpsc::Sender<TcpStream>,  // Receives visitor connections
+    // The control channel map and this channel's own key, so a dead control
+    // channel can evict itself instead of waiting for the client to reconnect.
+    // Eviction is by `session_key`, not `service_digest`: a reconnect
+    // generates a fresh `session_key` and replaces the digest-keyed entry
+    // before the old channel's watchdog notices it's dead, so evicting by
+    // digest would delete the new, live entry instead of the stale one.
+    control_channels: Arc<RwLock<ControlChannelMap<T>>>,
+    session_key: Nonce,
+    heartbeat_interval: Duration,
+    // `idle_count`/`pending_requests`: shared with the connection pool and fed
+    // into `requests_to_send` to decide when and how much to refill towards
+    // `service.pool.max`. See `ConnectionPoolHandle` for what each counts.
+    idle_count: Arc<AtomicUsize>,
+    pending_requests: Arc<Mutex<VecDeque<Instant>>>,
+    // Fires when the whole server starts shutting down, so this control channel
+    // can stop accepting visitors and requesting data channels, while any
+    // sessions already forwarding keep running independently until they finish
+    global_shutdown_rx: broadcast::Receiver<bool>,
+    // Each `u8` sent on this chan is a request to create a data channel. Shared
+    // with the connection pool, so it can also request a replacement when it
+    // discards a dead cached data channel
+    data_req_tx: mpsc::UnboundedSender<u8>,
+    data_req_rx: Option<mpsc::UnboundedReceiver<u8>>,
 }
 
 // The handle of a control channel, along with the handle of a connection pool
@@ -309,15 +428,26 @@ struct ControlChannelHandle<T: Transport> {
 impl<T: 'static + Transport> ControlChannelHandle<T> {
     // Create a control channel handle, where the control channel handling task
     // and the connection pool task are created.
-    fn new(conn: T::Stream, service: ServerServiceConfig) -> ControlChannelHandle<T> {
+    fn new(
+        conn: T::Stream,
+        service: ServerServiceConfig,
+        control_channels: Arc<RwLock<ControlChannelMap<T>>>,
+        session_key: Nonce,
+        heartbeat_interval: u64,
+        active_sessions: Arc<AtomicUsize>,
+        global_shutdown_rx: broadcast::Receiver<bool>,
+    ) -> ControlChannelHandle<T> {
         // Save the name string for logging
         let name = service.name.clone();
 
         // Create a shutdown channel. The sender is not used for now, but for future use
         let (_shutdown_tx, shutdown_rx) = oneshot::channel::<bool>();
 
+        // Each `u8` sent on this chan is a request to create a data channel
+        let (data_req_tx, data_req_rx) = mpsc::unbounded_channel::<u8>();
+
         // Create and run the connection pool, where the visitors and data channels meet
-        let conn_pool = ConnectionPoolHandle::new();
+        let conn_pool = ConnectionPoolHandle::new(active_sessions, data_req_tx.clone());
 
         // Create the control channel
         let ch: ControlChannel<T> = ControlChannel {
@@ -325,6 +455,14 @@ impl<T: 'static + Transport> ControlChannelHandle<T> {
             shutdown_rx,
             service,
             visitor_tx: conn_pool.visitor_tx.clone(),
+            control_channels,
+            session_key,
+            heartbeat_interval: Duration::from_secs(heartbeat_interval),
+            idle_count: conn_pool.idle_count.clone(),
+            pending_requests: conn_pool.pending_requests.clone(),
+            global_shutdown_rx,
+            data_req_tx,
+            data_req_rx: Some(data_req_rx),
         };
 
         // Run the control channel
@@ -345,6 +483,12 @@ impl<T: Transport> ControlChannel<T> {
     // Run a control channel
     #[tracing::instrument(skip(self), fields(service = %self.service.name))]
     async fn run(mut self) -> Result<()> {
+        // `time::interval` panics on a zero duration, and `heartbeat_interval == 0`
+        // is an easy way for someone to try to "disable" heartbeats via config
+        if self.heartbeat_interval.is_zero() {
+            bail!("`heartbeat_interval` must be greater than zero");
+        }
+
         // Where the service is exposed
         let l = match TcpListener::bind(&self.service.bind_addr).await {
             Ok(v) => v,
@@ -361,25 +505,89 @@ impl<T: Transport> ControlChannel<T> {
 
         info!("Listening at {}", &self.service.bind_addr);
 
-        // Each `u8` in the chan indicates a data channel creation request
-        let (data_req_tx, mut data_req_rx) = mpsc::unbounded_channel::<u8>();
+        // Shared with the connection pool, which also uses it to request a
+        // replacement when it discards a dead cached data channel
+        let mut data_req_rx = self
+            .data_req_rx
+            .take()
+            .expect("ControlChannel::run called more than once");
+
+        // Tracks the instant we last heard back from the client, via a `read_ack`
+        // following our heartbeat or `CreateDataChannel` write. Unlike tracking the
+        // write itself, this needs an actual round trip, so it still catches a
+        // connection that's gone silently dead (NAT timeout, client crash) even
+        // though local writes keep landing in the OS send buffer without error.
+        let last_ack = Arc::new(RwLock::new(Instant::now()));
+
+        // Split the connection so the writer below and the ack reader further down
+        // can each own a half and run concurrently without fighting over `self.conn`
+        let (mut conn_rd, mut conn_wr) = io::split(self.conn);
+
+        // Fires the instant the connection is observed closed (EOF or reset),
+        // so the watchdog doesn't have to wait for its next tick to notice
+        let (conn_closed_tx, mut conn_closed_rx) = oneshot::channel::<()>();
+
+        // Sends CreateDataChannel commands to the client when needed, and a
+        // Heartbeat on every tick so dead connections get noticed even when the
+        // service is otherwise idle. Liveness is judged by `conn_rd`'s ack reader
+        // below, not by these writes succeeding.
+        let heartbeat_interval = self.heartbeat_interval;
+        tokio::spawn(async move {
+            let create_cmd = bincode::serialize(&ControlChannelCmd::CreateDataChannel).unwrap();
+            let heartbeat_cmd = bincode::serialize(&ControlChannelCmd::Heartbeat).unwrap();
+            let mut heartbeat = time::interval(heartbeat_interval);
+            loop {
+                tokio::select! {
+                    req = data_req_rx.recv() => {
+                        match req {
+                            Some(_) => {
+                                if conn_wr.write_all(&create_cmd).await.is_err() {
+                                    break;
+                                }
+                            }
+                            None => break,
+                        }
+                    }
+                    _ = heartbeat.tick() => {
+                        if conn_wr.write_all(&heartbeat_cmd).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
 
-        // The control channel is moved into the task, and sends CreateDataChannel
-        // comamnds to the client when needed
+        // Reads the client's ack for every heartbeat/`CreateDataChannel` write,
+        // which is what makes `last_ack` a true round-trip liveness signal.
+        // It also stops once `_reader_stop_tx` is dropped when this function
+        // returns, releasing its half of the connection
+        let reader_last_ack = last_ack.clone();
+        let (_reader_stop_tx, mut reader_stop_rx) = oneshot::channel::<()>();
         tokio::spawn(async move {
-            let cmd = bincode::serialize(&ControlChannelCmd::CreateDataChannel).unwrap();
-            while data_req_rx.recv().await.is_some() {
-                if self.conn.write_all(&cmd).await.is_err() {
-                    break;
+            loop {
+                let ack = tokio::select! {
+                    ack = protocol::read_ack(&mut conn_rd) => ack,
+                    _ = &mut reader_stop_rx => break,
+                };
+                match ack {
+                    Ok(_) => {
+                        *reader_last_ack.write().await = Instant::now();
+                    }
+                    Err(_) => {
+                        // EOF or reset: the client is gone, no need to wait for the watchdog
+                        let _ = conn_closed_tx.send(());
+                        break;
+                    }
                 }
             }
         });
 
-        // Cache some data channels for later use
-        for _i in 0..POOL_SIZE {
-            if let Err(e) = data_req_tx.send(0) {
+        // Pre-warm the pool up to the configured high watermark
+        for _i in 0..self.service.pool.max {
+            if let Err(e) = self.data_req_tx.send(0) {
                 error!("Failed to request data channel {}", e);
             };
+            self.pending_requests.lock().await.push_back(Instant::now());
         }
 
         // Retry at least every 1s
@@ -389,8 +597,14 @@ impl<T: Transport> ControlChannel<T> {
             ..Default::default()
         };
 
+        // Fires periodically to check whether the client has gone quiet for too
+        // long, i.e. the ack reader hasn't seen an ack from it in a while
+        let mut watchdog = time::interval(self.heartbeat_interval);
+        let watchdog_timeout = self.heartbeat_interval * HEARTBEAT_WATCHDOG_MULTIPLIER;
+
         // Wait for visitors and the shutdown signal
-        loop {
+        let mut dead = false;
+        'ctrl: loop {
             tokio::select! {
                 // Wait for visitors
                 val = l.accept() => {
@@ -408,13 +622,24 @@ impl<T: Transport> ControlChannel<T> {
                             }
                         },
                         Ok((incoming, addr)) => {
-                            // For every visitor, request to create a data channel
-                            if let Err(e) = data_req_tx.send(0) {
-                                // An error indicates the control channel is broken
-                                // So break the loop
-                                error!("{}", e);
-                                break;
-                            };
+                            // Only top the pool back up once idle + already-requested
+                            // channels have dipped below the low watermark, batching the
+                            // refill up to the high watermark, instead of requesting
+                            // exactly one channel per visitor (which, since requests
+                            // take a round trip to fulfill, would let a burst of
+                            // visitors pile up far more requests than `pool.max`)
+                            let idle = self.idle_count.load(Ordering::SeqCst);
+                            let pending = self.pending_requests.lock().await.len();
+                            for _ in 0..requests_to_send(idle, pending, self.service.pool.min, self.service.pool.max) {
+                                if let Err(e) = self.data_req_tx.send(0) {
+                                    // An error indicates the control channel is broken
+                                    // So break the loop
+                                    error!("{}", e);
+                                    dead = true;
+                                    break 'ctrl;
+                                };
+                                self.pending_requests.lock().await.push_back(Instant::now());
+                            }
 
                             backoff.reset();
 
@@ -425,12 +650,65 @@ impl<T: Transport> ControlChannel<T> {
                         }
                     }
                 },
+                // Check that the client is still acking our heartbeats, and sweep
+                // out any pending requests that have been outstanding so long
+                // they're never getting fulfilled, so they stop counting towards
+                // the watermark in `requests_to_send` forever
+                _ = watchdog.tick() => {
+                    expire_pending_requests(&mut self.pending_requests.lock().await, watchdog_timeout);
+                    if last_ack.read().await.elapsed() > watchdog_timeout {
+                        warn!(
+                            "No heartbeat ack in {:?}, treating control channel as dead",
+                            watchdog_timeout
+                        );
+                        dead = true;
+                        break;
+                    }
+                }
+                // The connection was closed (EOF or reset) rather than going quiet,
+                // so there's no point waiting for the watchdog to time out
+                _ = &mut conn_closed_rx => {
+                    warn!("Control channel connection closed, treating control channel as dead");
+                    dead = true;
+                    break;
+                }
                 // Wait for the shutdown signal
                 _ = &mut self.shutdown_rx => {
                     break;
                 }
+                // Wait for the whole server to start shutting down. Breaking here
+                // only stops accepting new visitors and requesting new data
+                // channels; any session already forwarding through the connection
+                // pool keeps running until it finishes, since it's an independent
+                // spawned task tracked by `active_sessions`
+                _ = self.global_shutdown_rx.recv() => {
+                    info!("Server shutting down, draining in-flight sessions");
+                    // Not actually dead, but this control channel is going away
+                    // either way, so it needs the same cleanup: otherwise its
+                    // stale handle lingers in `control_channels` and the writer,
+                    // ack reader and connection pool tasks it drags along with it
+                    // (via their `visitor_tx`/`data_req_tx` senders) keep running
+                    dead = true;
+                    break;
+                }
             }
         }
+
+        if dead {
+            // The client never reports back when a control channel dies, so evict
+            // it here instead of waiting for the next accidental reconnect. This
+            // also drops `visitor_tx`, which is what lets the connection pool's
+            // task notice `visitor_rx.recv()` returning `None` and wind itself
+            // down, taking the writer task with it. The ack reader stops on its
+            // own once this function returns and drops `_reader_stop_tx`.
+            //
+            // Removing by `session_key` (rather than `service_digest`) means a
+            // reconnect that's already replaced this entry under the same
+            // digest, with a freshly generated `session_key`, is untouched:
+            // there's no longer an entry keyed by our `session_key` to remove.
+            self.control_channels.write().await.remove2(&self.session_key);
+        }
+
         info!("Service shuting down");
 
         Ok(())
@@ -441,20 +719,49 @@ impl<T: Transport> ControlChannel<T> {
 struct ConnectionPool<T: Transport> {
     visitor_rx: mpsc::Receiver<TcpStream>,
     data_ch_rx: mpsc::Receiver<T::Stream>,
+    idle_count: Arc<AtomicUsize>,
+    // See `ConnectionPoolHandle::pending_requests`. Popped from the front here
+    // as data channels arrive, and pushed here too when a dead cached one is
+    // discarded and replaced
+    pending_requests: Arc<Mutex<VecDeque<Instant>>>,
+    // Count of in-flight forwarding sessions, shared with `Server`, so a
+    // graceful shutdown knows when it's safe to stop waiting
+    active_sessions: Arc<AtomicUsize>,
+    // Lets the pool request a replacement when it discards a dead cached
+    // data channel, so the pool doesn't quietly shrink below its watermark
+    data_req_tx: mpsc::UnboundedSender<u8>,
 }
 
 struct ConnectionPoolHandle<T: Transport> {
     visitor_tx: mpsc::Sender<TcpStream>,
     data_ch_tx: mpsc::Sender<T::Stream>,
+    // Count of idle cached data channels sitting in `data_ch_rx`, reported back
+    // so `ControlChannel` can decide when the pool needs refilling
+    idle_count: Arc<AtomicUsize>,
+    // The instant each in-flight `CreateDataChannel` request was sent, oldest
+    // first, so `ControlChannel` doesn't re-request channels that are already
+    // on their way, and so a request that never gets fulfilled (lost packet,
+    // client failed to dial back, nonce mismatch) expires instead of
+    // permanently wedging `requests_to_send` shut. See `expire_pending_requests`.
+    pending_requests: Arc<Mutex<VecDeque<Instant>>>,
 }
 
 impl<T: 'static + Transport> ConnectionPoolHandle<T> {
-    fn new() -> ConnectionPoolHandle<T> {
+    fn new(
+        active_sessions: Arc<AtomicUsize>,
+        data_req_tx: mpsc::UnboundedSender<u8>,
+    ) -> ConnectionPoolHandle<T> {
         let (data_ch_tx, data_ch_rx) = mpsc::channel(CHAN_SIZE * 2);
         let (visitor_tx, visitor_rx) = mpsc::channel(CHAN_SIZE);
+        let idle_count = Arc::new(AtomicUsize::new(0));
+        let pending_requests = Arc::new(Mutex::new(VecDeque::new()));
         let conn_pool: ConnectionPool<T> = ConnectionPool {
             data_ch_rx,
             visitor_rx,
+            idle_count: idle_count.clone(),
+            pending_requests: pending_requests.clone(),
+            active_sessions,
+            data_req_tx,
         };
 
         tokio::spawn(async move { conn_pool.run().await });
@@ -462,24 +769,113 @@ impl<T: 'static + Transport> ConnectionPoolHandle<T> {
         ConnectionPoolHandle {
             data_ch_tx,
             visitor_tx,
+            idle_count,
+            pending_requests,
         }
     }
 }
 
+// Recycling a cached data channel that died while idle (NAT timeout, client
+// restart...) would hand the visitor a half-open connection. A zero-duration
+// read lets us observe an already-closed stream (EOF or reset) without
+// blocking on one that's simply been quiet.
+//
+// This relies on a protocol invariant: the client must never write anything
+// on a data channel before it receives our `StartForward` command. If it
+// did, that byte would be silently consumed by this probe instead of reaching
+// `copy_bidirectional`, corrupting the forwarded stream. A free function
+// (rather than a method on `ConnectionPool<T>`) so it's easy to exercise
+// directly against a mock stream in tests.
+async fn is_stream_alive<S: AsyncReadExt + Unpin>(stream: &mut S) -> bool {
+    let mut probe = [0u8; 1];
+    !matches!(
+        time::timeout(Duration::ZERO, stream.read(&mut probe)).await,
+        Ok(Ok(0)) | Ok(Err(_))
+    )
+}
+
 impl<T: Transport> ConnectionPool<T> {
     #[tracing::instrument]
     async fn run(mut self) {
         while let Some(mut visitor) = self.visitor_rx.recv().await {
-            if let Some(mut ch) = self.data_ch_rx.recv().await {
-                tokio::spawn(async move {
-                    let cmd = bincode::serialize(&DataChannelCmd::StartForward).unwrap();
-                    if ch.write_all(&cmd).await.is_ok() {
-                        let _ = copy_bidirectional(&mut ch, &mut visitor).await;
+            let ch = loop {
+                match self.data_ch_rx.recv().await {
+                    Some(mut candidate) => {
+                        self.idle_count.fetch_sub(1, Ordering::SeqCst);
+                        if is_stream_alive(&mut candidate).await {
+                            break Some(candidate);
+                        }
+                        debug!("Discarding a dead cached data channel");
+                        if let Err(e) = self.data_req_tx.send(0) {
+                            error!("Failed to request a replacement data channel {}", e);
+                        } else {
+                            self.pending_requests.lock().await.push_back(Instant::now());
+                        }
                     }
-                });
-            } else {
-                break;
-            }
+                    None => break None,
+                }
+            };
+
+            let mut ch = match ch {
+                Some(ch) => ch,
+                None => break,
+            };
+
+            let active_sessions = self.active_sessions.clone();
+            active_sessions.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(async move {
+                let cmd = bincode::serialize(&DataChannelCmd::StartForward).unwrap();
+                if ch.write_all(&cmd).await.is_ok() {
+                    let _ = copy_bidirectional(&mut ch, &mut visitor).await;
+                }
+                active_sessions.fetch_sub(1, Ordering::SeqCst);
+            });
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_to_send_tops_up_to_max_when_below_min() {
+        assert_eq!(requests_to_send(0, 0, 10, 20), 20);
+        // Already-pending requests count towards the top-up, so a burst of
+        // visitors doesn't keep piling requests on top of each other
+        assert_eq!(requests_to_send(5, 4, 10, 20), 11);
+    }
+
+    #[test]
+    fn requests_to_send_sends_nothing_at_or_above_min() {
+        assert_eq!(requests_to_send(10, 0, 10, 20), 0);
+        // Combined idle + pending is what matters, not idle alone
+        assert_eq!(requests_to_send(2, 18, 10, 20), 0);
+    }
+
+    #[test]
+    fn expire_pending_requests_drops_only_stale_entries() {
+        // Real sleeps, not tokio's paused clock: `Instant` is unaffected by it
+        let mut pending = VecDeque::new();
+        pending.push_back(Instant::now());
+        std::thread::sleep(Duration::from_millis(20));
+        pending.push_back(Instant::now());
+
+        expire_pending_requests(&mut pending, Duration::from_millis(10));
+
+        assert_eq!(pending.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn is_stream_alive_true_when_quiet() {
+        let (mut a, _b) = tokio::io::duplex(64);
+        assert!(is_stream_alive(&mut a).await);
+    }
+
+    #[tokio::test]
+    async fn is_stream_alive_false_on_eof() {
+        let (mut a, b) = tokio::io::duplex(64);
+        drop(b);
+        assert!(!is_stream_alive(&mut a).await);
+    }
+}
@@ -0,0 +1,183 @@
+// The transport layer abstraction `Server<T>` is generic over, plus its `quic`
+// implementation. `TcpTransport` and `TlsTransport` live alongside this trait
+// but are unchanged here; this file only adds what's needed for `QuicTransport`.
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::net::SocketAddr;
+
+/// What `Server<T>` needs from a transport: bind a listener, and hand back a
+/// `Stream` (an `AsyncRead + AsyncWrite`) per accepted connection.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    type Acceptor: Send + Sync;
+    type Stream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static;
+
+    async fn new(config: &crate::config::TransportConfig) -> Result<Box<Self>>
+    where
+        Self: Sized;
+    async fn bind(&self, addr: &str) -> Result<Self::Acceptor>;
+    async fn accept(&self, a: &Self::Acceptor) -> Result<(Self::Stream, SocketAddr)>;
+}
+
+#[cfg(feature = "quic")]
+mod quic {
+    use super::Transport;
+    use anyhow::{Context, Result};
+    use async_trait::async_trait;
+    use std::net::SocketAddr;
+    use std::pin::Pin;
+    use std::task::{Context as TaskContext, Poll};
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+    use tokio::sync::{mpsc, Mutex};
+
+    /// A single bidirectional QUIC stream, wrapped so it presents as one
+    /// `AsyncRead + AsyncWrite` object and drops into the same
+    /// `copy_bidirectional`/`write_all` call sites a `TcpStream` does today.
+    pub struct QuicBiStream {
+        send: quinn::SendStream,
+        recv: quinn::RecvStream,
+    }
+
+    impl AsyncRead for QuicBiStream {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut TaskContext<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.recv).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for QuicBiStream {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut TaskContext<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            Pin::new(&mut self.send).poll_write(cx, buf)
+        }
+
+        fn poll_flush(
+            mut self: Pin<&mut Self>,
+            cx: &mut TaskContext<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.send).poll_flush(cx)
+        }
+
+        fn poll_shutdown(
+            mut self: Pin<&mut Self>,
+            cx: &mut TaskContext<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.send).poll_shutdown(cx)
+        }
+    }
+
+    /// Feeds freshly opened bidirectional streams from every connection the
+    /// endpoint accepts into a single channel, so a visitor only ever costs a
+    /// new QUIC stream rather than a new QUIC connection (and survives the
+    /// client's IP/port changing mid-session, since `quinn` handles connection
+    /// migration for us).
+    pub struct QuicAcceptor {
+        stream_rx: Mutex<mpsc::Receiver<(QuicBiStream, SocketAddr)>>,
+    }
+
+    pub struct QuicTransport {
+        endpoint: quinn::Endpoint,
+    }
+
+    #[async_trait]
+    impl Transport for QuicTransport {
+        type Acceptor = QuicAcceptor;
+        type Stream = QuicBiStream;
+
+        async fn new(config: &crate::config::TransportConfig) -> Result<Box<Self>> {
+            let quic_config = config
+                .quic
+                .as_ref()
+                .with_context(|| "Missing `[server.transport.quic]` config")?;
+            let server_config = build_server_config(quic_config)
+                .await
+                .with_context(|| "Failed to build the QUIC server config")?;
+            let endpoint = quinn::Endpoint::server(server_config, "0.0.0.0:0".parse().unwrap())
+                .with_context(|| "Failed to create the QUIC endpoint")?;
+            Ok(Box::new(QuicTransport { endpoint }))
+        }
+
+        async fn bind(&self, addr: &str) -> Result<Self::Acceptor> {
+            let socket = std::net::UdpSocket::bind(addr)
+                .with_context(|| format!("Failed to bind the QUIC endpoint to {}", addr))?;
+            self.endpoint
+                .rebind(socket)
+                .with_context(|| "Failed to rebind the QUIC endpoint to `server.bind_addr`")?;
+
+            let endpoint = self.endpoint.clone();
+            let (stream_tx, stream_rx) = mpsc::channel(1024);
+            tokio::spawn(async move {
+                while let Some(connecting) = endpoint.accept().await {
+                    let stream_tx = stream_tx.clone();
+                    tokio::spawn(async move {
+                        let conn = match connecting.await {
+                            Ok(conn) => conn,
+                            Err(e) => {
+                                tracing::warn!("QUIC handshake failed: {}", e);
+                                return;
+                            }
+                        };
+                        let addr = conn.remote_address();
+                        while let Ok((send, recv)) = conn.accept_bi().await {
+                            if stream_tx
+                                .send((QuicBiStream { send, recv }, addr))
+                                .await
+                                .is_err()
+                            {
+                                // The acceptor side is gone, nothing left to feed
+                                return;
+                            }
+                        }
+                    });
+                }
+            });
+
+            Ok(QuicAcceptor {
+                stream_rx: Mutex::new(stream_rx),
+            })
+        }
+
+        async fn accept(&self, a: &Self::Acceptor) -> Result<(Self::Stream, SocketAddr)> {
+            a.stream_rx
+                .lock()
+                .await
+                .recv()
+                .await
+                .with_context(|| "QUIC endpoint closed")
+        }
+    }
+
+    // Certificate loading mirrors `TlsTransport`'s `cert_path`/`key_path` config
+    async fn build_server_config(config: &crate::config::QuicConfig) -> Result<quinn::ServerConfig> {
+        let cert_chain = load_certs(&config.cert_path)
+            .await
+            .with_context(|| format!("Failed to load `{}`", config.cert_path))?;
+        let key = load_key(&config.key_path)
+            .await
+            .with_context(|| format!("Failed to load `{}`", config.key_path))?;
+        quinn::ServerConfig::with_single_cert(cert_chain, key)
+            .with_context(|| "Failed to build the QUIC server config from the configured cert/key")
+    }
+
+    async fn load_certs(path: &str) -> Result<Vec<rustls::Certificate>> {
+        let data = tokio::fs::read(path).await?;
+        let certs = rustls_pemfile::certs(&mut data.as_slice())?;
+        Ok(certs.into_iter().map(rustls::Certificate).collect())
+    }
+
+    async fn load_key(path: &str) -> Result<rustls::PrivateKey> {
+        let data = tokio::fs::read(path).await?;
+        let mut keys = rustls_pemfile::pkcs8_private_keys(&mut data.as_slice())?;
+        let key = keys.pop().with_context(|| "No private key found")?;
+        Ok(rustls::PrivateKey(key))
+    }
+}
+
+#[cfg(feature = "quic")]
+pub use quic::QuicTransport;